@@ -16,54 +16,86 @@
 ///   - UniFFI can figure out the package/module names for each crate, eliminating the external
 ///     package maps.
 use crate::{
-    bindings::TargetLanguage, load_initial_config, macro_metadata, BindingGenerator,
-    BindingGeneratorDefault, BindingsConfig, ComponentInterface, Result,
+    load_initial_config, macro_metadata, BindingGenerator, BindingsConfig, ComponentInterface,
+    Result,
 };
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
+use std::collections::HashMap;
 use std::fs;
-use uniffi_meta::{create_metadata_groups, group_metadata};
+use std::io::Read;
+use uniffi_meta::{create_metadata_groups, group_metadata, Metadata, MetadataGroup};
 
 /// Generate foreign bindings
 ///
 /// Returns the list of sources used to generate the bindings, in no particular order.
-pub fn generate_bindings(
+///
+/// `binding_generator` is generic over [`BindingGenerator`], so external backends can reuse this
+/// whole dylib-metadata-discovery pipeline for their own target languages.
+///
+/// `config_file_override`, if set, is deep-merged on top of each crate's own `uniffi.toml` (if
+/// any), with the override's keys taking precedence.
+///
+/// If `crate_name` is set, only the matching source is generated; an error if no group in the
+/// library's metadata matches.
+pub fn generate_bindings<T: BindingGenerator + ?Sized>(
     library_path: &Utf8Path,
     crate_root: &Utf8Path,
-    target_languages: &[TargetLanguage],
+    binding_generator: &T,
+    config_file_override: Option<&Utf8Path>,
+    crate_name: Option<String>,
     out_dir: &Utf8Path,
     try_format_code: bool,
-) -> Result<Vec<Source<crate::Config>>> {
-    generate_external_bindings(
-        BindingGeneratorDefault {
-            target_languages: target_languages.into(),
-            try_format_code,
-        },
-        library_path,
+) -> Result<Vec<Source<T::Config>>> {
+    let cdylib_name = match calc_library_name(library_path) {
+        Some((name, LibraryKind::Cdylib)) => Some(name),
+        _ => None,
+    };
+    binding_generator.check_library_path(library_path, cdylib_name)?;
+
+    let mut sources = find_sources(
         crate_root,
-        out_dir,
-    )
+        library_path,
+        cdylib_name,
+        config_file_override,
+        crate_name,
+    )?;
+    fs::create_dir_all(out_dir)?;
+    write_all_bindings(binding_generator, &mut sources, out_dir, try_format_code)?;
+
+    Ok(sources)
 }
 
-/// Generate foreign bindings
-///
-/// Returns the list of sources used to generate the bindings, in no particular order.
+// Write bindings for each source, recording the paths written on `Source::out_paths`
+fn write_all_bindings<T: BindingGenerator + ?Sized>(
+    binding_generator: &T,
+    sources: &mut [Source<T::Config>],
+    out_dir: &Utf8Path,
+    try_format_code: bool,
+) -> Result<()> {
+    for source in sources.iter_mut() {
+        source.out_paths =
+            binding_generator.write_bindings(&source.ci, &source.config, out_dir, try_format_code)?;
+    }
+    Ok(())
+}
+
+/// Generate foreign bindings for an external [`BindingGenerator`]
+#[deprecated(note = "use generate_bindings instead")]
 pub fn generate_external_bindings<T: BindingGenerator>(
     binding_generator: T,
     library_path: &Utf8Path,
     crate_root: &Utf8Path,
     out_dir: &Utf8Path,
 ) -> Result<Vec<Source<T::Config>>> {
-    let cdylib_name = calc_cdylib_name(library_path);
-    binding_generator.check_library_path(library_path, cdylib_name)?;
-
-    let sources = find_sources(crate_root, library_path, cdylib_name)?;
-    fs::create_dir_all(out_dir)?;
-
-    for source in sources.iter() {
-        binding_generator.write_bindings(&source.ci, &source.config, out_dir, None)?;
-    }
-
-    Ok(sources)
+    generate_bindings(
+        library_path,
+        crate_root,
+        &binding_generator,
+        None,
+        None,
+        out_dir,
+        true,
+    )
 }
 
 // A single source that we generate bindings for
@@ -72,19 +104,39 @@ pub struct Source<Config: BindingsConfig> {
     pub crate_name: String,
     pub ci: ComponentInterface,
     pub config: Config,
+    /// Paths of the binding files written for this source, one per target language
+    pub out_paths: Vec<Utf8PathBuf>,
+}
+
+// The kind of library found at a `library_path`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LibraryKind {
+    Cdylib,
+    Staticlib,
 }
 
 // If `library_path` is a C dynamic library, return its name
+#[deprecated(note = "use calc_library_name instead")]
 pub fn calc_cdylib_name(library_path: &Utf8Path) -> Option<&str> {
-    let cdylib_extentions = [".so", ".dll", ".dylib"];
+    match calc_library_name(library_path) {
+        Some((name, LibraryKind::Cdylib)) => Some(name),
+        _ => None,
+    }
+}
+
+// If `library_path` is a C dynamic library or a static library archive, return its name and kind
+pub fn calc_library_name(library_path: &Utf8Path) -> Option<(&str, LibraryKind)> {
     let filename = library_path.file_name()?;
     let filename = filename.strip_prefix("lib").unwrap_or(filename);
+    let cdylib_extentions = [".so", ".dll", ".dylib"];
     for ext in cdylib_extentions {
         if let Some(f) = filename.strip_suffix(ext) {
-            return Some(f);
+            return Some((f, LibraryKind::Cdylib));
         }
     }
-    None
+    filename
+        .strip_suffix(".a")
+        .map(|f| (f, LibraryKind::Staticlib))
 }
 
 fn find_sources<Config: BindingsConfig>(
@@ -92,18 +144,28 @@ fn find_sources<Config: BindingsConfig>(
     crate_root: &Utf8Path,
     library_path: &Utf8Path,
     cdylib_name: Option<&str>,
+    config_file_override: Option<&Utf8Path>,
+    crate_name: Option<String>,
 ) -> Result<Vec<Source<Config>>> {
-    let items = macro_metadata::extract_from_library(library_path)?;
+    let items = match calc_library_name(library_path) {
+        Some((_, LibraryKind::Staticlib)) => extract_from_staticlib(library_path)?,
+        _ => macro_metadata::extract_from_library(library_path)?,
+    };
     let mut metadata_groups = create_metadata_groups(&items);
     group_metadata(&mut metadata_groups, items)?;
 
+    let metadata_groups = match crate_name {
+        Some(crate_name) => filter_by_crate_name(metadata_groups, library_path, &crate_name)?,
+        None => metadata_groups,
+    };
+
     metadata_groups
         .into_values()
         .map(|group| {
             let crate_name = group.namespace.crate_name.clone();
             let mut ci = ComponentInterface::new(&crate_name);
             ci.add_metadata(group)?;
-            let mut config = load_initial_config::<Config>(crate_root, None)?;
+            let mut config = load_initial_config::<Config>(crate_root, config_file_override)?;
             if let Some(cdylib_name) = cdylib_name {
                 config.update_from_cdylib_name(cdylib_name);
             }
@@ -112,31 +174,164 @@ fn find_sources<Config: BindingsConfig>(
                 config,
                 crate_name,
                 ci,
+                out_paths: Vec::new(),
             })
         })
         .collect()
 }
 
+// Keep only the metadata group for `crate_name`, erroring out if it isn't found
+fn filter_by_crate_name(
+    mut metadata_groups: HashMap<String, MetadataGroup>,
+    library_path: &Utf8Path,
+    crate_name: &str,
+) -> Result<HashMap<String, MetadataGroup>> {
+    metadata_groups.retain(|_, group| group.namespace.crate_name == crate_name);
+    if metadata_groups.is_empty() {
+        anyhow::bail!("Unable to find metadata for crate {crate_name} in {library_path}");
+    }
+    Ok(metadata_groups)
+}
+
+// Unpack a `.a` archive's object file members and extract UniFFI metadata from each one
+fn extract_from_staticlib(library_path: &Utf8Path) -> Result<Vec<Metadata>> {
+    extract_from_staticlib_members(library_path, macro_metadata::extract_from_library)
+}
+
+// Like `extract_from_staticlib`, but with the per-member extraction step injectable for testing
+fn extract_from_staticlib_members(
+    library_path: &Utf8Path,
+    extract_member: impl Fn(&Utf8Path) -> Result<Vec<Metadata>>,
+) -> Result<Vec<Metadata>> {
+    let archive_bytes = fs::read(library_path)?;
+    let mut archive = ar::Archive::new(archive_bytes.as_slice());
+    let tmp_dir = camino_tempfile::tempdir()?;
+    let mut items = Vec::new();
+    let mut member_index = 0;
+    while let Some(entry) = archive.next_entry() {
+        let mut entry = entry?;
+        let identifier = String::from_utf8_lossy(entry.header().identifier()).into_owned();
+        if is_archive_bookkeeping_member(&identifier) {
+            continue;
+        }
+        let member_path: Utf8PathBuf = tmp_dir.path().join(format!("member-{member_index}.o"));
+        member_index += 1;
+        let mut object_bytes = Vec::new();
+        entry.read_to_end(&mut object_bytes)?;
+        fs::write(&member_path, &object_bytes)?;
+        items.extend(extract_member(&member_path)?);
+    }
+    Ok(items)
+}
+
+// `ar`/`ranlib`/rustc archives carry a GNU (`/`, `/SYM64/`) or BSD (`__.SYMDEF`) symbol-table
+// member, plus a `//` extended-filename table when member names don't fit the 16-byte short
+// name field -- which is the common case for rustc's per-codegen-unit object files. None of
+// these are object files, so skip them rather than handing them to the object-file parser.
+fn is_archive_bookkeeping_member(identifier: &str) -> bool {
+    matches!(identifier, "/" | "//" | "/SYM64/") || identifier.starts_with("__.SYMDEF")
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
-    fn calc_cdylib_name_is_correct() {
+    fn calc_library_name_is_correct() {
+        assert_eq!(
+            ("uniffi", LibraryKind::Cdylib),
+            calc_library_name("/path/to/libuniffi.so".into()).unwrap()
+        );
         assert_eq!(
-            "uniffi",
-            calc_cdylib_name("/path/to/libuniffi.so".into()).unwrap()
+            ("uniffi", LibraryKind::Cdylib),
+            calc_library_name("/path/to/libuniffi.dylib".into()).unwrap()
         );
         assert_eq!(
-            "uniffi",
-            calc_cdylib_name("/path/to/libuniffi.dylib".into()).unwrap()
+            ("uniffi", LibraryKind::Cdylib),
+            calc_library_name("/path/to/uniffi.dll".into()).unwrap()
         );
         assert_eq!(
-            "uniffi",
-            calc_cdylib_name("/path/to/uniffi.dll".into()).unwrap()
+            ("uniffi", LibraryKind::Staticlib),
+            calc_library_name("/path/to/libuniffi.a".into()).unwrap()
         );
     }
 
+    fn metadata_group_for(crate_name: &str) -> MetadataGroup {
+        MetadataGroup {
+            namespace: uniffi_meta::NamespaceMetadata {
+                crate_name: crate_name.to_string(),
+                name: crate_name.to_string(),
+            },
+            items: Default::default(),
+        }
+    }
+
+    #[test]
+    fn filter_by_crate_name_keeps_only_the_matching_group() {
+        let groups = HashMap::from([
+            ("crate_a".to_string(), metadata_group_for("crate_a")),
+            ("crate_b".to_string(), metadata_group_for("crate_b")),
+        ]);
+
+        let filtered = filter_by_crate_name(groups, "/path/to/libuniffi.so".into(), "crate_a")
+            .expect("crate_a is present");
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered["crate_a"].namespace.crate_name, "crate_a");
+    }
+
+    #[test]
+    fn filter_by_crate_name_errors_when_no_group_matches() {
+        let groups = HashMap::from([("crate_a".to_string(), metadata_group_for("crate_a"))]);
+
+        let err = filter_by_crate_name(groups, "/path/to/libuniffi.so".into(), "crate_missing")
+            .unwrap_err();
+        assert!(err.to_string().contains("crate_missing"));
+    }
+
+    #[test]
+    fn extract_from_staticlib_members_skips_bookkeeping_entries() {
+        use std::cell::RefCell;
+
+        let mut archive_bytes = Vec::new();
+        {
+            let mut builder = ar::Builder::new(&mut archive_bytes);
+            builder
+                .append(&ar::Header::new(b"/".to_vec(), 4), &b"xxxx"[..])
+                .unwrap();
+            builder
+                .append(&ar::Header::new(b"//".to_vec(), 4), &b"yyyy"[..])
+                .unwrap();
+            builder
+                .append(&ar::Header::new(b"real.o".to_vec(), 4), &b"obj0"[..])
+                .unwrap();
+        }
+
+        let tmp_dir = camino_tempfile::tempdir().unwrap();
+        let library_path = tmp_dir.path().join("libtest.a");
+        fs::write(&library_path, &archive_bytes).unwrap();
+
+        let extracted_from = RefCell::new(Vec::new());
+        let items = extract_from_staticlib_members(&library_path, |member_path| {
+            extracted_from.borrow_mut().push(member_path.to_owned());
+            Ok(Vec::new())
+        })
+        .unwrap();
+
+        assert!(items.is_empty());
+        assert_eq!(extracted_from.borrow().len(), 1);
+    }
+
+    #[test]
+    fn is_archive_bookkeeping_member_skips_symbol_and_filename_tables() {
+        assert!(is_archive_bookkeeping_member("/"));
+        assert!(is_archive_bookkeeping_member("//"));
+        assert!(is_archive_bookkeeping_member("/SYM64/"));
+        assert!(is_archive_bookkeeping_member("__.SYMDEF"));
+        assert!(is_archive_bookkeeping_member("__.SYMDEF SORTED"));
+        assert!(!is_archive_bookkeeping_member("foo.o"));
+        assert!(!is_archive_bookkeeping_member("uniffi-123456.o"));
+    }
+
     /// Right now we unconditionally strip the `lib` prefix.
     ///
     /// Technically Windows DLLs do not start with a `lib` prefix,
@@ -144,10 +339,59 @@ mod test {
     /// On Linux/macOS this would result in a `liblibuniffi.{so,dylib}` file.
     #[test]
     #[ignore] // Currently fails.
-    fn calc_cdylib_name_is_correct_on_windows() {
+    fn calc_library_name_is_correct_on_windows() {
+        assert_eq!(
+            ("libuniffi", LibraryKind::Cdylib),
+            calc_library_name("/path/to/libuniffi.dll".into()).unwrap()
+        );
+    }
+
+    #[derive(Debug, Default, Clone)]
+    struct TestConfig;
+
+    impl BindingsConfig for TestConfig {
+        fn update_from_ci(&mut self, _ci: &ComponentInterface) {}
+        fn update_from_cdylib_name(&mut self, _cdylib_name: &str) {}
+    }
+
+    struct TestGenerator;
+
+    impl BindingGenerator for TestGenerator {
+        type Config = TestConfig;
+
+        fn check_library_path(
+            &self,
+            _library_path: &Utf8Path,
+            _cdylib_name: Option<&str>,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        fn write_bindings(
+            &self,
+            ci: &ComponentInterface,
+            _config: &Self::Config,
+            out_dir: &Utf8Path,
+            _try_format_code: bool,
+        ) -> Result<Vec<Utf8PathBuf>> {
+            Ok(vec![out_dir.join(format!("{}.bindings", ci.crate_name()))])
+        }
+    }
+
+    #[test]
+    fn write_all_bindings_records_the_paths_write_bindings_returns() {
+        let mut sources = vec![Source {
+            crate_name: "test_crate".to_string(),
+            ci: ComponentInterface::new("test_crate"),
+            config: TestConfig,
+            out_paths: Vec::new(),
+        }];
+
+        write_all_bindings(&TestGenerator, &mut sources, "/out".into(), true).unwrap();
+
         assert_eq!(
-            "libuniffi",
-            calc_cdylib_name("/path/to/libuniffi.dll".into()).unwrap()
+            sources[0].out_paths,
+            vec![Utf8PathBuf::from("/out/test_crate.bindings")]
         );
     }
 }